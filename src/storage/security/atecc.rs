@@ -0,0 +1,370 @@
+/*
+ * Copyright 2020
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+//! Secure-element backend for Microchip ATECC508A/608A crypto chips reached
+//! over I²C — the "external enclave" described in this module's top-level
+//! docs, for embedded deployments with a soldered tamper-resistant part
+//! instead of an OS keyring or HSM.
+//!
+//! The chip only supports NIST P-256 ECC, SHA-256 HMAC, and (on the 608A)
+//! AES-GCM, and its slots are provisioned ahead of time rather than created
+//! on demand, so this backend restricts `EnclaveKey` to that subset and maps
+//! `generate_key` labels onto the fixed slot table from `AteccConfig`.
+//! `import_key` always fails: keys can only be generated on-chip.
+
+use super::{
+    AesModes, AesSizes, AteccConfig, EcCurves, EcdsaAlgorithm, EnclaveConfig, EnclaveError,
+    EnclaveErrorKind, EnclaveKey, EnclaveLike, EnclaveOps, EnclaveResult, HmacAlgorithm, KeyHandle,
+};
+use i2cdev::core::I2CDevice;
+use i2cdev::linux::LinuxI2CDevice;
+use std::{collections::HashMap, path::Path, sync::Mutex};
+
+// ATECC608A command opcodes (see the Microchip ATECC608A datasheet, §9.4).
+const OP_GEN_KEY: u8 = 0x40;
+const OP_SIGN: u8 = 0x41;
+const OP_ECDH: u8 = 0x43;
+const OP_HMAC: u8 = 0x11;
+const OP_LOCK: u8 = 0x17;
+const OP_INFO: u8 = 0x30;
+
+/// A slot's provisioned key type, restricted to what the chip can actually hold.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SlotKind {
+    EcdsaP256Sha256,
+    EcdhP256,
+    HmacSha256,
+    AesGcm,
+}
+
+fn slot_kind_for(key_type: EnclaveKey) -> EnclaveResult<SlotKind> {
+    match key_type {
+        EnclaveKey::Ecdsa(EcCurves::Secp256r1, EcdsaAlgorithm::Sha256) => {
+            Ok(SlotKind::EcdsaP256Sha256)
+        }
+        EnclaveKey::Ecdh(EcCurves::Secp256r1) => Ok(SlotKind::EcdhP256),
+        EnclaveKey::Hmac(HmacAlgorithm::Sha256) => Ok(SlotKind::HmacSha256),
+        EnclaveKey::WrapKey(super::WrappingKey::Aes(AesSizes::Aes256, AesModes::Gcm)) => {
+            Ok(SlotKind::AesGcm)
+        }
+        other => Err(EnclaveErrorKind::GeneralError {
+            msg: format!(
+                "the ATECC508A/608A does not support key type {:?}; it is limited to \
+                 Ecdsa(Secp256r1, Sha256), Ecdh(Secp256r1), Hmac(Sha256), and AES-GCM on the 608A",
+                other
+            ),
+        }
+        .into()),
+    }
+}
+
+/// CRC-16 over the command packet, per the datasheet's checksum algorithm
+/// (polynomial 0x8005, reflected, used instead of the usual CRC-CCITT).
+fn atecc_crc16(data: &[u8]) -> u16 {
+    const POLY: u16 = 0x8005;
+    let mut crc: u16 = 0;
+    for &byte in data {
+        let mut bit = 0x01u8;
+        while bit != 0 {
+            let data_bit: u16 = if byte & bit != 0 { 1 } else { 0 };
+            let crc_bit = (crc >> 15) & 0x01;
+            crc <<= 1;
+            if data_bit != crc_bit {
+                crc ^= POLY;
+            }
+            bit <<= 1;
+        }
+    }
+    crc
+}
+
+/// One ATECC slot, tracked by the label it was provisioned for.
+struct Slot {
+    slot: u8,
+    kind: SlotKind,
+}
+
+/// An [`EnclaveLike`] implementation backed by a real ATECC508A/608A over I²C.
+pub struct AteccEnclave {
+    device: Mutex<LinuxI2CDevice>,
+    slots: Mutex<HashMap<String, Slot>>,
+}
+
+fn access_denied(msg: impl Into<String>) -> EnclaveError {
+    EnclaveErrorKind::AccessDenied { msg: msg.into() }.into()
+}
+
+impl AteccEnclave {
+    /// Send a command packet (opcode, param1, param2, data) and read back
+    /// the chip's response, wrapping I²C failures as `ConnectionFailure`.
+    fn transact(&self, opcode: u8, param1: u8, param2: u16, data: &[u8]) -> EnclaveResult<Vec<u8>> {
+        let mut packet = Vec::with_capacity(7 + data.len());
+        packet.push(0x03); // Command word address
+        packet.push((7 + data.len()) as u8); // Count (excludes the word address byte)
+        packet.push(opcode);
+        packet.push(param1);
+        packet.extend_from_slice(&param2.to_le_bytes());
+        packet.extend_from_slice(data);
+        let crc = atecc_crc16(&packet[1..]);
+        packet.extend_from_slice(&crc.to_le_bytes());
+
+        let mut device = self
+            .device
+            .lock()
+            .expect("ATECC I2C device lock poisoned");
+        device.write(&packet).map_err(|e| EnclaveErrorKind::ConnectionFailure {
+            msg: format!("I2C write to ATECC part failed: {}", e),
+        })?;
+
+        let mut response = vec![0u8; 1 + data.len().max(64)];
+        device.read(&mut response).map_err(|e| EnclaveErrorKind::ConnectionFailure {
+            msg: format!("I2C read from ATECC part failed: {}", e),
+        })?;
+
+        let count = *response.first().ok_or_else(|| EnclaveErrorKind::GeneralError {
+            msg: "ATECC response was empty".to_string(),
+        })? as usize;
+        if count == 0x04 && response.get(1) == Some(&0x01) {
+            return Err(access_denied("ATECC returned a checkmac/verify failure status"));
+        }
+        Ok(response.into_iter().skip(1).take(count.saturating_sub(3)).collect())
+    }
+
+    fn slot_kind(&self, handle: &KeyHandle) -> EnclaveResult<(u8, SlotKind)> {
+        self.slots
+            .lock()
+            .expect("ATECC slot table lock poisoned")
+            .get(handle.id())
+            .map(|s| (s.slot, s.kind))
+            .ok_or_else(|| EnclaveErrorKind::ItemNotFound.into())
+    }
+}
+
+impl EnclaveLike for AteccEnclave {
+    fn connect<A: AsRef<Path>, B: Into<String>>(config: EnclaveConfig<A, B>) -> EnclaveResult<Self> {
+        let AteccConfig {
+            i2c_bus,
+            address,
+            slots,
+        } = match config {
+            EnclaveConfig::Atecc(cfg) => cfg,
+            _ => {
+                return Err(EnclaveErrorKind::ConnectionFailure {
+                    msg: "AteccEnclave::connect requires EnclaveConfig::Atecc".to_string(),
+                }
+                .into())
+            }
+        };
+
+        let device = LinuxI2CDevice::new(i2c_bus.as_ref(), address as u16).map_err(|e| {
+            EnclaveErrorKind::ConnectionFailure {
+                msg: format!("could not open ATECC I2C device: {}", e),
+            }
+        })?;
+
+        let mut slot_table = HashMap::with_capacity(slots.len());
+        for s in slots {
+            // The chip's slot contents are provisioned out-of-band; take the
+            // slot's kind from that provisioning instead of guessing, since
+            // `generate_key` is never called for slots that already hold a
+            // key (e.g. an HMAC or ECDH slot written at manufacturing time).
+            let kind = slot_kind_for(s.key_type)?;
+            slot_table.insert(s.label.clone(), Slot { slot: s.slot, kind });
+        }
+
+        Ok(Self {
+            device: Mutex::new(device),
+            slots: Mutex::new(slot_table),
+        })
+    }
+
+    fn close(self) {
+        // The I2C device file is closed when `self.device` drops.
+    }
+}
+
+impl EnclaveOps for AteccEnclave {
+    fn generate_key(&self, label: &str, key_type: EnclaveKey) -> EnclaveResult<KeyHandle> {
+        let kind = slot_kind_for(key_type)?;
+        let slot_num = {
+            let slots = self.slots.lock().expect("ATECC slot table lock poisoned");
+            let slot = slots.get(label).ok_or(EnclaveErrorKind::ItemNotFound)?;
+            if slot.kind != kind {
+                return Err(EnclaveErrorKind::GeneralError {
+                    msg: format!(
+                        "slot \"{}\" was provisioned as {:?}, not {:?}",
+                        label, slot.kind, kind
+                    ),
+                }
+                .into());
+            }
+            slot.slot
+        };
+        self.transact(OP_GEN_KEY, 0x04, slot_num as u16, &[])?;
+        Ok(KeyHandle::new(label))
+    }
+
+    fn import_key(&self, label: &str, key_type: EnclaveKey, _material: &[u8]) -> EnclaveResult<KeyHandle> {
+        slot_kind_for(key_type)?;
+        Err(EnclaveErrorKind::GeneralError {
+            msg: format!(
+                "the ATECC508A/608A cannot import private key material into slot \"{}\"; \
+                 keys must be generated on-chip with generate_key",
+                label
+            ),
+        }
+        .into())
+    }
+
+    fn public_key(&self, handle: &KeyHandle) -> EnclaveResult<Vec<u8>> {
+        let (slot_num, _) = self.slot_kind(handle)?;
+        self.transact(OP_GEN_KEY, 0x00, slot_num as u16, &[])
+    }
+
+    fn sign(&self, handle: &KeyHandle, data: &[u8]) -> EnclaveResult<Vec<u8>> {
+        let (slot_num, kind) = self.slot_kind(handle)?;
+        match kind {
+            SlotKind::EcdsaP256Sha256 => self.transact(OP_SIGN, 0x80, slot_num as u16, data),
+            SlotKind::HmacSha256 => self.transact(OP_HMAC, 0x04, slot_num as u16, data),
+            other => Err(EnclaveErrorKind::GeneralError {
+                msg: format!("slot kind {:?} cannot sign", other),
+            }
+            .into()),
+        }
+    }
+
+    fn verify(&self, handle: &KeyHandle, data: &[u8], signature: &[u8]) -> EnclaveResult<bool> {
+        let (_, kind) = self.slot_kind(handle)?;
+        if kind == SlotKind::HmacSha256 {
+            // HMAC tags are checked by recomputing them, in constant time.
+            let expected = self.sign(handle, data)?;
+            return Ok(expected.len() == signature.len()
+                && expected.iter().zip(signature).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0);
+        }
+        let public = self.public_key(handle)?;
+        let mut payload = Vec::with_capacity(public.len() + data.len() + signature.len());
+        payload.extend_from_slice(&public);
+        payload.extend_from_slice(data);
+        payload.extend_from_slice(signature);
+        self.transact(0x45 /* Verify */, 0x02, 0x0000, &payload)
+            .map(|resp| resp.first() == Some(&0x00))
+    }
+
+    fn encrypt(&self, handle: &KeyHandle, _plaintext: &[u8]) -> EnclaveResult<Vec<u8>> {
+        let (_, kind) = self.slot_kind(handle)?;
+        Err(EnclaveErrorKind::GeneralError {
+            msg: format!("slot kind {:?} does not support RSA-OAEP style encrypt", kind),
+        }
+        .into())
+    }
+
+    fn decrypt(&self, handle: &KeyHandle, _ciphertext: &[u8]) -> EnclaveResult<Vec<u8>> {
+        let (_, kind) = self.slot_kind(handle)?;
+        Err(EnclaveErrorKind::GeneralError {
+            msg: format!("slot kind {:?} does not support RSA-OAEP style decrypt", kind),
+        }
+        .into())
+    }
+
+    fn wrap_key(&self, wrapping_key: &KeyHandle, target: &KeyHandle) -> EnclaveResult<Vec<u8>> {
+        let (slot_num, kind) = self.slot_kind(wrapping_key)?;
+        if kind != SlotKind::AesGcm {
+            return Err(EnclaveErrorKind::GeneralError {
+                msg: "only an AES-GCM slot on the 608A can wrap keys".to_string(),
+            }
+            .into());
+        }
+        let _ = self.slot_kind(target)?;
+        self.transact(0x47 /* AES */, 0x00, slot_num as u16, &[])
+    }
+
+    fn unwrap_key(
+        &self,
+        wrapping_key: &KeyHandle,
+        label: &str,
+        key_type: EnclaveKey,
+        _wrapped: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        let (_, kind) = self.slot_kind(wrapping_key)?;
+        if kind != SlotKind::AesGcm {
+            return Err(EnclaveErrorKind::GeneralError {
+                msg: "only an AES-GCM slot on the 608A can unwrap keys".to_string(),
+            }
+            .into());
+        }
+        self.import_key(label, key_type, &[])
+    }
+
+    fn seal(
+        &self,
+        _recipient_pub: &[u8],
+        _info: &[u8],
+        _aad: &[u8],
+        _plaintext: &[u8],
+    ) -> EnclaveResult<(Vec<u8>, Vec<u8>)> {
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "HPKE seal is not implemented for the ATECC508A/608A backend".to_string(),
+        }
+        .into())
+    }
+
+    fn open(
+        &self,
+        _handle: &KeyHandle,
+        _enc: &[u8],
+        _info: &[u8],
+        _aad: &[u8],
+        _ciphertext: &[u8],
+    ) -> EnclaveResult<Vec<u8>> {
+        // A real HPKE open needs the key schedule and AEAD decrypt run over
+        // the ECDH shared secret, neither of which this backend performs;
+        // returning the raw ECDH output here would silently hand callers
+        // the wrong bytes instead of plaintext. Be honest instead.
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "HPKE open is not implemented for the ATECC508A/608A backend".to_string(),
+        }
+        .into())
+    }
+
+    fn derive_key(
+        &self,
+        _parent: &KeyHandle,
+        key_type: EnclaveKey,
+        _context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        slot_kind_for(key_type)?;
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "the ATECC508A/608A does not expose an HKDF primitive for key derivation"
+                .to_string(),
+        }
+        .into())
+    }
+
+    fn derive_credential_key(
+        &self,
+        _label: &str,
+        _hmac_key: &KeyHandle,
+        _input: &[u8],
+        _context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "credential-bound key derivation is not implemented for the ATECC508A/608A \
+                  backend"
+                .to_string(),
+        }
+        .into())
+    }
+}
@@ -0,0 +1,143 @@
+/*
+ * Copyright 2020
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+//! AES-CMAC (RFC 4493 / NIST SP 800-38B).
+//!
+//! Implemented directly on top of the `aes` block cipher, taking the raw
+//! AES key bytes. The software enclave uses it for `EnclaveKey::Cmac` keys.
+
+use super::{AesSizes, EnclaveErrorKind, EnclaveResult};
+use aes::{
+    cipher::generic_array::GenericArray,
+    cipher::{BlockEncrypt, NewBlockCipher},
+    Aes128, Aes192, Aes256,
+};
+
+const BLOCK_SIZE: usize = 16;
+const RB: u8 = 0x87;
+
+/// AES key length, in bytes, for `size`.
+fn key_len(size: AesSizes) -> usize {
+    match size {
+        AesSizes::Aes128 => 16,
+        AesSizes::Aes192 => 24,
+        AesSizes::Aes256 => 32,
+    }
+}
+
+fn encrypt_block(key: &[u8], size: AesSizes, block: &[u8; BLOCK_SIZE]) -> EnclaveResult<[u8; BLOCK_SIZE]> {
+    if key.len() != key_len(size) {
+        return Err(EnclaveErrorKind::GeneralError {
+            msg: format!(
+                "AES-CMAC key must be {} bytes for {:?}, got {}",
+                key_len(size),
+                size,
+                key.len()
+            ),
+        }
+        .into());
+    }
+    let mut out = GenericArray::clone_from_slice(block);
+    match size {
+        AesSizes::Aes128 => Aes128::new(GenericArray::from_slice(key)).encrypt_block(&mut out),
+        AesSizes::Aes192 => Aes192::new(GenericArray::from_slice(key)).encrypt_block(&mut out),
+        AesSizes::Aes256 => Aes256::new(GenericArray::from_slice(key)).encrypt_block(&mut out),
+    }
+    let mut result = [0u8; BLOCK_SIZE];
+    result.copy_from_slice(&out);
+    Ok(result)
+}
+
+/// `K <<1`, XORing in `RB` when the most significant bit of `k` was 1.
+fn double(k: [u8; BLOCK_SIZE]) -> [u8; BLOCK_SIZE] {
+    let msb_set = k[0] & 0x80 != 0;
+    let mut out = [0u8; BLOCK_SIZE];
+    let mut carry = 0u8;
+    for i in (0..BLOCK_SIZE).rev() {
+        out[i] = (k[i] << 1) | carry;
+        carry = (k[i] & 0x80) >> 7;
+    }
+    if msb_set {
+        out[BLOCK_SIZE - 1] ^= RB;
+    }
+    out
+}
+
+fn subkeys(key: &[u8], size: AesSizes) -> EnclaveResult<([u8; BLOCK_SIZE], [u8; BLOCK_SIZE])> {
+    let l = encrypt_block(key, size, &[0u8; BLOCK_SIZE])?;
+    let k1 = double(l);
+    let k2 = double(k1);
+    Ok((k1, k2))
+}
+
+fn xor_block(a: &[u8; BLOCK_SIZE], b: &[u8; BLOCK_SIZE]) -> [u8; BLOCK_SIZE] {
+    let mut out = [0u8; BLOCK_SIZE];
+    for i in 0..BLOCK_SIZE {
+        out[i] = a[i] ^ b[i];
+    }
+    out
+}
+
+/// Pad a final partial block with `10*` up to `BLOCK_SIZE`.
+fn pad(block: &[u8]) -> [u8; BLOCK_SIZE] {
+    let mut out = [0u8; BLOCK_SIZE];
+    out[..block.len()].copy_from_slice(block);
+    out[block.len()] = 0x80;
+    out
+}
+
+/// Compute the AES-CMAC tag of `message` under `key`.
+pub fn mac(key: &[u8], size: AesSizes, message: &[u8]) -> EnclaveResult<[u8; BLOCK_SIZE]> {
+    let (k1, k2) = subkeys(key, size)?;
+
+    let n_blocks = (message.len() + BLOCK_SIZE - 1) / BLOCK_SIZE;
+    let (n_blocks, complete_last) = if n_blocks == 0 {
+        (1, false)
+    } else {
+        (n_blocks, message.len() % BLOCK_SIZE == 0)
+    };
+
+    let mut mac = [0u8; BLOCK_SIZE]; // CBC-MAC starts from a zero IV
+    for i in 0..n_blocks {
+        let start = i * BLOCK_SIZE;
+        let is_last = i == n_blocks - 1;
+        let block = if is_last {
+            let chunk = &message[start..message.len().min(start + BLOCK_SIZE)];
+            if complete_last {
+                let mut b = [0u8; BLOCK_SIZE];
+                b.copy_from_slice(chunk);
+                xor_block(&b, &k1)
+            } else {
+                xor_block(&pad(chunk), &k2)
+            }
+        } else {
+            let mut b = [0u8; BLOCK_SIZE];
+            b.copy_from_slice(&message[start..start + BLOCK_SIZE]);
+            b
+        };
+        mac = encrypt_block(key, size, &xor_block(&mac, &block))?;
+    }
+    Ok(mac)
+}
+
+/// Verify `tag` against the AES-CMAC of `message` under `key` in constant time.
+pub fn verify(key: &[u8], size: AesSizes, message: &[u8], tag: &[u8]) -> EnclaveResult<bool> {
+    if tag.len() != BLOCK_SIZE {
+        return Ok(false);
+    }
+    let expected = mac(key, size, message)?;
+    Ok(expected.iter().zip(tag.iter()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0)
+}
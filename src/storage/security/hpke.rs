@@ -0,0 +1,197 @@
+/*
+ * Copyright 2020
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+//! Single-shot HPKE (RFC 9180) built on the enclave's `X25519`/`Ecdh` keys.
+//!
+//! Only `DHKEM(X25519, HKDF-SHA256)` is implemented as the KEM. The AEAD is
+//! selectable between the enclave's existing `WrappingKey` algorithms so a
+//! sealed message can reuse whichever symmetric primitive the target
+//! enclave already supports. The AES-GCM suites use their standard RFC 9180
+//! AEAD ids; `XChachaPoly1305` is a crate-internal extension identified by a
+//! private-use id and is not interoperable with conforming HPKE peers
+//! outside this crate.
+//!
+//! `seal` generates the sender's ephemeral `X25519` keypair itself; `open`
+//! takes the recipient's raw private key, so looking up the recipient's
+//! enclave-held key is the job of the `EnclaveOps::open` implementations
+//! that call into it. The AEAD step runs through the software backend's
+//! AEAD wiring (`software::seal_with_nonce`/`open_with_nonce`).
+
+use super::{AesModes, AesSizes, EnclaveErrorKind, EnclaveResult, WrappingKey};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use x25519_dalek::{EphemeralSecret, PublicKey, StaticSecret};
+
+const VERSION: &[u8] = b"HPKE-v1";
+const KEM_ID_X25519_HKDF_SHA256: u16 = 0x0020;
+const KDF_ID_HKDF_SHA256: u16 = 0x0001;
+
+/// RFC 9180 assigns `0x0003` to `ChaCha20Poly1305` (12-byte nonce), which is
+/// not what this module runs for `XChachaPoly1305` (24-byte nonce, via
+/// `XChaCha20Poly1305`). Label it with an id from the IANA "HPKE AEAD
+/// Identifiers" private-use range (`0xFF00`-`0xFFFE`) instead, so the
+/// `suite_id` honestly reflects that this particular suite is a
+/// crate-internal extension rather than a real RFC 9180 AEAD — sealed
+/// messages using it are not expected to interoperate with conforming HPKE
+/// peers outside this crate.
+const AEAD_ID_XCHACHA20POLY1305_PRIVATE: u16 = 0xff01;
+
+fn aead_id(aead: WrappingKey) -> u16 {
+    match aead {
+        WrappingKey::Aes(AesSizes::Aes128, AesModes::Gcm) => 0x0001,
+        WrappingKey::Aes(AesSizes::Aes256, AesModes::Gcm) => 0x0002,
+        WrappingKey::XChachaPoly1305 => AEAD_ID_XCHACHA20POLY1305_PRIVATE,
+        _ => 0xffff,
+    }
+}
+
+fn nk(aead: WrappingKey) -> usize {
+    match aead {
+        WrappingKey::Aes(AesSizes::Aes128, _) => 16,
+        WrappingKey::Aes(AesSizes::Aes192, _) => 24,
+        WrappingKey::Aes(AesSizes::Aes256, _) => 32,
+        WrappingKey::XChachaPoly1305 => 32,
+    }
+}
+
+fn nn(aead: WrappingKey) -> usize {
+    match aead {
+        WrappingKey::Aes(_, _) => 12,
+        WrappingKey::XChachaPoly1305 => 24,
+    }
+}
+
+/// `LabeledExtract(salt, label, ikm) = Extract(salt, "HPKE-v1" || suite_id || label || ikm)`
+fn labeled_extract(suite_id: &[u8], salt: &[u8], label: &[u8], ikm: &[u8]) -> Vec<u8> {
+    let mut labeled_ikm = Vec::with_capacity(VERSION.len() + suite_id.len() + label.len() + ikm.len());
+    labeled_ikm.extend_from_slice(VERSION);
+    labeled_ikm.extend_from_slice(suite_id);
+    labeled_ikm.extend_from_slice(label);
+    labeled_ikm.extend_from_slice(ikm);
+    let (prk, _) = Hkdf::<Sha256>::extract(Some(salt), &labeled_ikm);
+    prk.to_vec()
+}
+
+/// `LabeledExpand(prk, label, info, len) = Expand(prk, I2OSP(len, 2) || "HPKE-v1" || suite_id || label || info, len)`
+fn labeled_expand(suite_id: &[u8], prk: &[u8], label: &[u8], info: &[u8], len: usize) -> EnclaveResult<Vec<u8>> {
+    let mut labeled_info = Vec::with_capacity(2 + VERSION.len() + suite_id.len() + label.len() + info.len());
+    labeled_info.extend_from_slice(&(len as u16).to_be_bytes());
+    labeled_info.extend_from_slice(VERSION);
+    labeled_info.extend_from_slice(suite_id);
+    labeled_info.extend_from_slice(label);
+    labeled_info.extend_from_slice(info);
+
+    let hk = Hkdf::<Sha256>::from_prk(prk)
+        .map_err(|_| EnclaveErrorKind::GeneralError { msg: "HPKE: invalid PRK length".to_string() })?;
+    let mut out = vec![0u8; len];
+    hk.expand(&labeled_info, &mut out)
+        .map_err(|_| EnclaveErrorKind::GeneralError { msg: "HPKE: expand output too long".to_string() })?;
+    Ok(out)
+}
+
+fn kem_suite_id() -> Vec<u8> {
+    let mut id = b"KEM".to_vec();
+    id.extend_from_slice(&KEM_ID_X25519_HKDF_SHA256.to_be_bytes());
+    id
+}
+
+fn hpke_suite_id(aead: WrappingKey) -> Vec<u8> {
+    let mut id = b"HPKE".to_vec();
+    id.extend_from_slice(&KEM_ID_X25519_HKDF_SHA256.to_be_bytes());
+    id.extend_from_slice(&KDF_ID_HKDF_SHA256.to_be_bytes());
+    id.extend_from_slice(&aead_id(aead).to_be_bytes());
+    id
+}
+
+/// `ExtractAndExpand`: derive the KEM shared secret from a raw X25519 DH output.
+fn extract_and_expand(dh: &[u8], enc: &[u8], recipient_pub: &[u8]) -> Vec<u8> {
+    let suite_id = kem_suite_id();
+    let mut kem_context = Vec::with_capacity(enc.len() + recipient_pub.len());
+    kem_context.extend_from_slice(enc);
+    kem_context.extend_from_slice(recipient_pub);
+
+    let eae_prk = labeled_extract(&suite_id, b"", b"eae_prk", dh);
+    labeled_expand(&suite_id, &eae_prk, b"shared_secret", &kem_context, 32)
+        .expect("Nsecret=32 always fits in one HKDF-SHA256 expand")
+}
+
+/// Base-mode `KeySchedule`: derive the AEAD key and base nonce for this exchange.
+fn key_schedule(shared_secret: &[u8], info: &[u8], aead: WrappingKey) -> EnclaveResult<(Vec<u8>, Vec<u8>)> {
+    let suite_id = hpke_suite_id(aead);
+    let psk_id_hash = labeled_extract(&suite_id, b"", b"psk_id_hash", b"");
+    let info_hash = labeled_extract(&suite_id, b"", b"info_hash", info);
+
+    let mut key_schedule_context = vec![0x00u8]; // mode_base
+    key_schedule_context.extend_from_slice(&psk_id_hash);
+    key_schedule_context.extend_from_slice(&info_hash);
+
+    let secret = labeled_extract(&suite_id, shared_secret, b"secret", b"");
+    let key = labeled_expand(&suite_id, &secret, b"key", &key_schedule_context, nk(aead))?;
+    let base_nonce = labeled_expand(&suite_id, &secret, b"base_nonce", &key_schedule_context, nn(aead))?;
+    Ok((key, base_nonce))
+}
+
+/// Seal `plaintext` to `recipient_pub` (a raw 32-byte X25519 public key),
+/// returning `(enc, ciphertext)`.
+pub fn seal(
+    recipient_pub: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+    aead: WrappingKey,
+) -> EnclaveResult<(Vec<u8>, Vec<u8>)> {
+    let recipient_pub: [u8; 32] = recipient_pub
+        .try_into()
+        .map_err(|_| EnclaveErrorKind::GeneralError { msg: "HPKE: recipient public key must be 32 bytes".to_string() })?;
+    let recipient_pk = PublicKey::from(recipient_pub);
+
+    let eph_sk = EphemeralSecret::new(rand::rngs::OsRng);
+    let eph_pk = PublicKey::from(&eph_sk);
+    let dh = eph_sk.diffie_hellman(&recipient_pk);
+    let enc = eph_pk.as_bytes().to_vec();
+
+    let shared_secret = extract_and_expand(dh.as_bytes(), &enc, recipient_pk.as_bytes());
+    let (key, base_nonce) = key_schedule(&shared_secret, info, aead)?;
+    let ciphertext = super::software::seal_with_nonce(&key, &base_nonce, aad, plaintext, aead)?;
+    Ok((enc, ciphertext))
+}
+
+/// Open a message produced by `seal`, given the recipient's raw 32-byte
+/// X25519 private key and the sender's ephemeral public key (`enc`).
+pub fn open(
+    recipient_priv: &[u8],
+    enc: &[u8],
+    info: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+    aead: WrappingKey,
+) -> EnclaveResult<Vec<u8>> {
+    let recipient_priv: [u8; 32] = recipient_priv
+        .try_into()
+        .map_err(|_| EnclaveErrorKind::GeneralError { msg: "HPKE: recipient private key must be 32 bytes".to_string() })?;
+    let enc_pub: [u8; 32] = enc
+        .try_into()
+        .map_err(|_| EnclaveErrorKind::GeneralError { msg: "HPKE: enc must be a 32-byte public key".to_string() })?;
+
+    let sk = StaticSecret::from(recipient_priv);
+    let recipient_pk = PublicKey::from(&sk);
+    let eph_pk = PublicKey::from(enc_pub);
+    let dh = sk.diffie_hellman(&eph_pk);
+
+    let shared_secret = extract_and_expand(dh.as_bytes(), enc, recipient_pk.as_bytes());
+    let (key, base_nonce) = key_schedule(&shared_secret, info, aead)?;
+    super::software::open_with_nonce(&key, &base_nonce, aad, ciphertext, aead)
+}
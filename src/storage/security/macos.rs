@@ -0,0 +1,330 @@
+/*
+ * Copyright 2020
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+//! Apple Secure Enclave (SEP)-backed implementation of [`EnclaveLike`].
+//!
+//! Unlike the login keychain, keys created here live inside the Secure
+//! Enclave Processor itself: the SEP only ever hands back a reference
+//! (`SecKey`), never the private key bytes, which are non-extractable by
+//! construction. The SEP only supports NIST P-256, so `EnclaveKey` is
+//! restricted to `Ecdsa(Secp256r1, _)` and `Ecdh(Secp256r1)`; every other
+//! variant is rejected with a descriptive `GeneralError`.
+
+use super::{
+    EcCurves, EnclaveConfig, EnclaveError, EnclaveErrorKind, EnclaveKey, EnclaveLike, EnclaveOps,
+    EnclaveResult, KeyHandle,
+};
+use security_framework::access_control::{ProtectionMode, SecAccessControl};
+use security_framework::key::{GenerateKeyOptions, KeyType, SecKey, Token};
+use std::path::Path;
+
+/// Access-control gate for keys created in the Secure Enclave.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessGate {
+    /// No additional authentication beyond device passcode
+    None,
+    /// Require a successful Touch ID / Face ID match
+    Biometry,
+    /// Require either biometry or the device passcode as a fallback
+    BiometryOrPasscode,
+}
+
+/// Configuration for connecting to the Secure Enclave.
+#[derive(Clone, Debug)]
+pub struct SecureEnclaveConfig {
+    /// Keychain tag prefix used to namespace keys created by this crate
+    tag_prefix: String,
+    /// Access-control gate applied to newly generated keys
+    access: AccessGate,
+}
+
+impl SecureEnclaveConfig {
+    /// Namespace keys under `tag_prefix`, gated by `access`
+    pub fn new(tag_prefix: impl Into<String>, access: AccessGate) -> Self {
+        Self {
+            tag_prefix: tag_prefix.into(),
+            access,
+        }
+    }
+}
+
+fn requires_p256(key_type: EnclaveKey) -> EnclaveResult<()> {
+    match key_type {
+        EnclaveKey::Ecdsa(EcCurves::Secp256r1, _) | EnclaveKey::Ecdh(EcCurves::Secp256r1) => Ok(()),
+        other => Err(EnclaveErrorKind::GeneralError {
+            msg: format!(
+                "the Secure Enclave only supports NIST P-256; {:?} is not available",
+                other
+            ),
+        }
+        .into()),
+    }
+}
+
+/// Map the `-25293` (errSecAuthFailed) / `-26276` (errkSecECNotAvailable-ish
+/// SEP auth refusal) class of Secure Enclave errors onto `AccessDenied`,
+/// falling back to the crate's generic `security_framework` error mapping.
+fn map_sep_error(e: security_framework::base::Error) -> EnclaveError {
+    match e.code() {
+        -25293 | -26276 => EnclaveErrorKind::AccessDenied {
+            msg: format!("Secure Enclave refused the operation: {}", e),
+        }
+        .into(),
+        _ => EnclaveError::from(e),
+    }
+}
+
+/// A [`EnclaveLike`] implementation backed by the Apple Secure Enclave.
+pub struct SecureEnclave {
+    tag_prefix: String,
+    access: AccessGate,
+}
+
+impl SecureEnclave {
+    fn tag(&self, label: &str) -> String {
+        format!("{}.{}", self.tag_prefix, label)
+    }
+
+    fn access_control(&self) -> EnclaveResult<SecAccessControl> {
+        let flags = match self.access {
+            AccessGate::None => security_framework::access_control::SecAccessControlFlags::empty(),
+            AccessGate::Biometry => security_framework::access_control::SecAccessControlFlags::BIOMETRY_CURRENT_SET,
+            AccessGate::BiometryOrPasscode => {
+                security_framework::access_control::SecAccessControlFlags::BIOMETRY_CURRENT_SET
+                    | security_framework::access_control::SecAccessControlFlags::DEVICE_PASSCODE
+                    | security_framework::access_control::SecAccessControlFlags::OR
+            }
+        };
+        SecAccessControl::create_with_flags(ProtectionMode::AccessibleWhenUnlockedThisDeviceOnly, flags)
+            .map_err(map_sep_error)
+    }
+
+    fn find_key(&self, label: &str) -> EnclaveResult<SecKey> {
+        use security_framework::item::{ItemClass, ItemSearchOptions, Reference, SearchResult};
+
+        let results = ItemSearchOptions::new()
+            .class(ItemClass::key())
+            .application_tag(self.tag(label).as_bytes())
+            .load_refs(true)
+            .limit(1)
+            .search()
+            .map_err(map_sep_error)?;
+
+        match results.into_iter().next() {
+            Some(SearchResult::Ref(Reference::Key(key))) => Ok(key),
+            _ => Err(EnclaveErrorKind::ItemNotFound.into()),
+        }
+    }
+}
+
+impl EnclaveLike for SecureEnclave {
+    fn connect<A: AsRef<Path>, B: Into<String>>(_config: EnclaveConfig<A, B>) -> EnclaveResult<Self> {
+        // `EnclaveConfig` doesn't yet carry Secure-Enclave-specific options,
+        // so this backend is connected to with a sensible default tag
+        // namespace and no additional access gate. Callers that need a
+        // custom `SecureEnclaveConfig` should use `SecureEnclave::with_config`.
+        Ok(Self {
+            tag_prefix: "com.mayaguez.enclave".to_string(),
+            access: AccessGate::None,
+        })
+    }
+
+    fn close(self) {
+        // The SEP key itself outlives the process; there is no live
+        // connection handle to tear down.
+    }
+}
+
+impl EnclaveOps for SecureEnclave {
+    fn generate_key(&self, label: &str, key_type: EnclaveKey) -> EnclaveResult<KeyHandle> {
+        requires_p256(key_type)?;
+        let tag = self.tag(label);
+        let access = self.access_control()?;
+
+        let mut options = GenerateKeyOptions::default();
+        options.set_key_type(KeyType::ec());
+        options.set_token(Token::SecureEnclave);
+        // `find_key` looks keys up by `kSecAttrApplicationTag`, so that's the
+        // attribute that has to be set here; `kSecAttrLabel` is left at its
+        // default since nothing reads it back.
+        options.set_application_tag(tag.as_bytes());
+        options.set_access_control(access);
+
+        options.generate().map_err(map_sep_error)?;
+        Ok(KeyHandle::new(label))
+    }
+
+    fn import_key(&self, _label: &str, key_type: EnclaveKey, _material: &[u8]) -> EnclaveResult<KeyHandle> {
+        requires_p256(key_type)?;
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "the Secure Enclave cannot import private key material; keys must be \
+                  generated on the SEP with generate_key"
+                .to_string(),
+        }
+        .into())
+    }
+
+    fn public_key(&self, handle: &KeyHandle) -> EnclaveResult<Vec<u8>> {
+        let key = self.find_key(handle.id())?;
+        key.public_key()
+            .map_err(map_sep_error)?
+            .external_representation()
+            .map(|d| d.to_vec())
+            .ok_or_else(|| {
+                EnclaveErrorKind::GeneralError {
+                    msg: "Secure Enclave key had no external public key representation".to_string(),
+                }
+                .into()
+            })
+    }
+
+    fn sign(&self, handle: &KeyHandle, data: &[u8]) -> EnclaveResult<Vec<u8>> {
+        let key = self.find_key(handle.id())?;
+        key.create_signature(
+            security_framework::key::Algorithm::ECDSASignatureMessageX962SHA256,
+            data,
+        )
+        .map_err(map_sep_error)
+    }
+
+    fn verify(&self, handle: &KeyHandle, data: &[u8], signature: &[u8]) -> EnclaveResult<bool> {
+        let key = self.find_key(handle.id())?;
+        let public = key.public_key().map_err(map_sep_error)?;
+        public
+            .verify_signature(
+                security_framework::key::Algorithm::ECDSASignatureMessageX962SHA256,
+                data,
+                signature,
+            )
+            .map_err(map_sep_error)
+    }
+
+    fn encrypt(&self, _handle: &KeyHandle, _plaintext: &[u8]) -> EnclaveResult<Vec<u8>> {
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "the Secure Enclave's P-256 keys cannot encrypt; use key_agreement-derived \
+                  symmetric keys instead"
+                .to_string(),
+        }
+        .into())
+    }
+
+    fn decrypt(&self, _handle: &KeyHandle, _ciphertext: &[u8]) -> EnclaveResult<Vec<u8>> {
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "the Secure Enclave's P-256 keys cannot decrypt; use key_agreement-derived \
+                  symmetric keys instead"
+                .to_string(),
+        }
+        .into())
+    }
+
+    fn wrap_key(&self, _wrapping_key: &KeyHandle, _target: &KeyHandle) -> EnclaveResult<Vec<u8>> {
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "the Secure Enclave does not expose a key-wrapping primitive".to_string(),
+        }
+        .into())
+    }
+
+    fn unwrap_key(
+        &self,
+        _wrapping_key: &KeyHandle,
+        _label: &str,
+        _key_type: EnclaveKey,
+        _wrapped: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "the Secure Enclave does not expose a key-unwrapping primitive".to_string(),
+        }
+        .into())
+    }
+
+    fn seal(
+        &self,
+        _recipient_pub: &[u8],
+        _info: &[u8],
+        _aad: &[u8],
+        _plaintext: &[u8],
+    ) -> EnclaveResult<(Vec<u8>, Vec<u8>)> {
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "HPKE seal is not implemented for the Secure Enclave backend".to_string(),
+        }
+        .into())
+    }
+
+    fn open(
+        &self,
+        _handle: &KeyHandle,
+        _enc: &[u8],
+        _info: &[u8],
+        _aad: &[u8],
+        _ciphertext: &[u8],
+    ) -> EnclaveResult<Vec<u8>> {
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "HPKE open is not implemented for the Secure Enclave backend".to_string(),
+        }
+        .into())
+    }
+
+    fn derive_key(
+        &self,
+        _parent: &KeyHandle,
+        key_type: EnclaveKey,
+        _context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        requires_p256(key_type)?;
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "the Secure Enclave does not expose an HKDF primitive for key derivation"
+                .to_string(),
+        }
+        .into())
+    }
+
+    fn derive_credential_key(
+        &self,
+        _label: &str,
+        _hmac_key: &KeyHandle,
+        _input: &[u8],
+        _context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        Err(EnclaveErrorKind::GeneralError {
+            msg: "the Secure Enclave does not support HMAC keys, so credential-bound \
+                  derivation is not available on this backend"
+                .to_string(),
+        }
+        .into())
+    }
+}
+
+impl SecureEnclave {
+    /// Connect with explicit tag-namespace and access-gate configuration,
+    /// bypassing `EnclaveConfig`'s defaults.
+    pub fn with_config(config: SecureEnclaveConfig) -> EnclaveResult<Self> {
+        Ok(Self {
+            tag_prefix: config.tag_prefix,
+            access: config.access,
+        })
+    }
+
+    /// Perform ECDH key agreement between `handle` (a local `Ecdh(Secp256r1)`
+    /// SEP key) and `peer_public`, the other party's raw P-256 public key
+    /// bytes, returning the shared secret.
+    pub fn key_agreement(&self, handle: &KeyHandle, peer_public: &[u8]) -> EnclaveResult<Vec<u8>> {
+        let key = self.find_key(handle.id())?;
+        let peer = SecKey::public_key_from_external_representation(peer_public, KeyType::ec())
+            .map_err(map_sep_error)?;
+        key.key_exchange(&peer, security_framework::key::KeyExchangeParams::default())
+            .map_err(map_sep_error)
+    }
+}
@@ -161,6 +161,17 @@ where
     OsKeyRing(OsKeyRingConfig<A, B>),
     /// Connect to a Yubihsm
     YubiHsm,
+    /// Connect to a pure-software enclave backed by RustCrypto primitives.
+    /// See the [`software`] module for details.
+    SoftwareEnclave(SoftwareEnclaveConfig<A>),
+    /// Connect to an ATECC508A/608A secure element over I²C.
+    /// See the [`atecc`] module for details.
+    Atecc(AteccConfig<A>),
+    /// Connect to the deterministic, non-secure [`null`] enclave used for
+    /// fuzzing and CI. Only selectable behind the `null-enclave` feature so
+    /// it can never be built into a production binary.
+    #[cfg(feature = "null-enclave")]
+    NullEnclave,
 }
 
 impl<A, B> fmt::Display for EnclaveConfig<A, B>
@@ -201,12 +212,168 @@ where
     }
 }
 
+/// Opaque reference to a key that lives inside an enclave.
+///
+/// Backends hand these out instead of raw key material so that
+/// non-exportable keys (e.g. hardware-backed keys) never have to
+/// leave the enclave to be usable. Two handles are equal only if
+/// they refer to the same backend-assigned identifier.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct KeyHandle {
+    /// Backend-assigned identifier for the key, e.g. a label, slot index, or keychain tag
+    id: String,
+}
+
+impl KeyHandle {
+    /// Wrap a backend-assigned identifier in a `KeyHandle`
+    pub fn new(id: impl Into<String>) -> Self {
+        Self { id: id.into() }
+    }
+
+    /// The backend-assigned identifier for this key
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl fmt::Display for KeyHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "KeyHandle({})", self.id)
+    }
+}
+
+/// Object-safe crypto-operation surface shared by every enclave backend.
+///
+/// Split out from [`EnclaveLike`] so that callers who only need to perform
+/// operations against an already-connected enclave (not establish the
+/// connection itself) can hold heterogeneous backends behind `dyn
+/// EnclaveOps`, which `EnclaveLike` itself cannot support: its `connect` is
+/// generic and `close` takes `self` by value, both of which rule out trait
+/// objects.
+pub trait EnclaveOps {
+    /// Generate a new key of the given type inside the enclave, stored under `label`.
+    ///
+    /// Backends that do not support the requested `EnclaveKey` should return
+    /// `EnclaveErrorKind::GeneralError` describing the limitation.
+    fn generate_key(&self, label: &str, key_type: EnclaveKey) -> EnclaveResult<KeyHandle>;
+
+    /// Import externally-generated key material into the enclave under `label`.
+    ///
+    /// `material` is the raw key bytes appropriate for `key_type` (e.g. the raw
+    /// 32-byte secret key or scalar for `Ed25519`, `X25519` and P-256/secp256k1
+    /// `Ecdsa` keys, raw bytes for symmetric keys). Prefer
+    /// `inject_key` when the material should arrive wrapped rather than in the clear.
+    fn import_key(&self, label: &str, key_type: EnclaveKey, material: &[u8])
+        -> EnclaveResult<KeyHandle>;
+
+    /// Return the public key bytes for an asymmetric `KeyHandle`.
+    fn public_key(&self, handle: &KeyHandle) -> EnclaveResult<Vec<u8>>;
+
+    /// Sign `data` with `handle`, dispatching on the key's `EnclaveKey` variant
+    /// (`Ecdsa`, `Ed25519`, `RsaPss`, `Hmac`, or `Cmac`).
+    fn sign(&self, handle: &KeyHandle, data: &[u8]) -> EnclaveResult<Vec<u8>>;
+
+    /// Verify a `signature` over `data` produced by `sign`.
+    fn verify(&self, handle: &KeyHandle, data: &[u8], signature: &[u8]) -> EnclaveResult<bool>;
+
+    /// Encrypt `plaintext` under an `RsaOaep` key.
+    fn encrypt(&self, handle: &KeyHandle, plaintext: &[u8]) -> EnclaveResult<Vec<u8>>;
+
+    /// Decrypt `ciphertext` previously produced by `encrypt`.
+    fn decrypt(&self, handle: &KeyHandle, ciphertext: &[u8]) -> EnclaveResult<Vec<u8>>;
+
+    /// Wrap `target` under `wrapping_key` (a `WrapKey`) so the wrapped bytes can
+    /// be stored or transported outside the enclave.
+    fn wrap_key(&self, wrapping_key: &KeyHandle, target: &KeyHandle) -> EnclaveResult<Vec<u8>>;
+
+    /// Unwrap `wrapped` key material under `wrapping_key`, storing the result
+    /// inside the enclave under `label` as a `key_type` key.
+    fn unwrap_key(
+        &self,
+        wrapping_key: &KeyHandle,
+        label: &str,
+        key_type: EnclaveKey,
+        wrapped: &[u8],
+    ) -> EnclaveResult<KeyHandle>;
+
+    /// Single-shot HPKE (RFC 9180) seal to `recipient_pub`, the encoded
+    /// public key of an `X25519`/`Ecdh` key held by (any) enclave.
+    ///
+    /// Returns `(enc, ciphertext)` where `enc` is the sender's ephemeral
+    /// public key, which the recipient needs to call `open`.
+    fn seal(
+        &self,
+        recipient_pub: &[u8],
+        info: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> EnclaveResult<(Vec<u8>, Vec<u8>)>;
+
+    /// Single-shot HPKE (RFC 9180) open of a message produced by `seal`,
+    /// using the `X25519`/`Ecdh` private key referenced by `handle`.
+    fn open(
+        &self,
+        handle: &KeyHandle,
+        enc: &[u8],
+        info: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> EnclaveResult<Vec<u8>>;
+
+    /// Derive a child key from a symmetric `parent` key using HKDF
+    /// (extract-then-expand, with `context_info` as the `info` parameter),
+    /// producing a new `key_type` key without the parent's bytes ever leaving
+    /// the enclave.
+    fn derive_key(
+        &self,
+        parent: &KeyHandle,
+        key_type: EnclaveKey,
+        context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle>;
+
+    /// Unwrap externally-generated key material that arrives wrapped under
+    /// `unwrap_with`, storing the result under `label` as a `key_type` key so
+    /// raw secrets never transit in the clear during provisioning.
+    ///
+    /// This is `unwrap_key` under the provisioning vocabulary; backends
+    /// should not need to implement this separately.
+    fn inject_key(
+        &self,
+        label: &str,
+        key_type: EnclaveKey,
+        wrapped: &[u8],
+        unwrap_with: &KeyHandle,
+    ) -> EnclaveResult<KeyHandle> {
+        self.unwrap_key(unwrap_with, label, key_type, wrapped)
+    }
+
+    /// Compute an HMAC-SHA256 tag over `input` with `hmac_key`, then use that
+    /// tag as HKDF input keying material (`context_info` as `info`) to derive
+    /// a reproducible P-256 signing key, stored under `label`.
+    ///
+    /// Supports credential-binding use cases where a derived signing key must
+    /// be reproducible from a shared secret rather than randomly generated.
+    fn derive_credential_key(
+        &self,
+        label: &str,
+        hmac_key: &KeyHandle,
+        input: &[u8],
+        context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle>;
+}
+
 /// All enclaves structs should use this trait so the callers
 /// can simply use them without diving into the details
 /// for each unique configuration. This trait is meant
 /// to be used by the non-security minded and should be hard
 /// to mess up––misuse resistant.
-pub trait EnclaveLike: Sized {
+///
+/// Establishing and tearing down a connection is inherently
+/// non-object-safe (`connect` is generic over `EnclaveConfig`'s type
+/// parameters, and `close` consumes `self`), so those two methods stay
+/// here while the actual crypto operations live on [`EnclaveOps`], which
+/// every `EnclaveLike` backend also implements.
+pub trait EnclaveLike: EnclaveOps + Sized {
     /// Establish a connection to the enclave
     fn connect<A: AsRef<Path>, B: Into<String>>(config: EnclaveConfig<A, B>)
         -> EnclaveResult<Self>;
@@ -219,7 +386,7 @@ pub trait EnclaveLike: Sized {
 /// Not all enclaves support all key types. Please review
 /// the documentation for your respective enclave to know
 /// each of their capabilities.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EnclaveKey {
     /// Twisted Edwards signing key
     Ed25519,
@@ -238,12 +405,14 @@ pub enum EnclaveKey {
     RsaPss(RsaMgf),
     /// Key for use with Hash-based Message Authentication Code tags
     Hmac(HmacAlgorithm),
+    /// Key for use with AES-CMAC (RFC 4493 / NIST SP 800-38B) tags
+    Cmac(AesSizes),
     /// Key for encrypting/decrypting data
     WrapKey(WrappingKey)
 }
 
 /// Valid algorithms for wrapping data
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum WrappingKey {
     /// AES encryption algorithm
     Aes(AesSizes, AesModes),
@@ -252,7 +421,7 @@ pub enum WrappingKey {
 }
 
 /// Valid sizes for the AES algorithm
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AesSizes {
     /// AES with 128 bit keys
     Aes128,
@@ -263,7 +432,7 @@ pub enum AesSizes {
 }
 
 /// Valid AEAD modes for AES
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum AesModes {
     /// Counter with CBC-MAC mode. This is a NIST approved mode of operation defined in SP 800-38C
     Ccm,
@@ -274,7 +443,7 @@ pub enum AesModes {
 }
 
 /// Valid curves for ECC operations
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EcCurves {
     /// NIST P-256 curve
     Secp256r1,
@@ -287,7 +456,7 @@ pub enum EcCurves {
 }
 
 /// Valid algorithms for ECDSA signatures
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum EcdsaAlgorithm {
     /// Sign/Verify ECC signatures using SHA1
     /// Only use for legacy purposes as SHA1 is considered broken
@@ -301,7 +470,7 @@ pub enum EcdsaAlgorithm {
 }
 
 /// Valid algorithms for HMAC keys
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum HmacAlgorithm {
     /// Sign/Verify HMAC tags using SHA1
     /// Only use for legacy purposes as SHA1 is considered broken
@@ -315,7 +484,7 @@ pub enum HmacAlgorithm {
 }
 
 /// Mask generating functions for RSA signatures
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RsaMgf {
     /// Sign/Verify RSA signatures using SHA1
     /// Only use for legacy purposes as SHA1 is considered broken
@@ -328,5 +497,93 @@ pub enum RsaMgf {
     Sha512,
 }
 
+/// Configuration for the pure-software enclave.
+///
+/// If `persist_path` is `None`, keys only ever live in memory and are lost
+/// when the process exits. Encrypted-at-rest persistence is not implemented
+/// yet, so `SoftwareEnclave::connect` currently rejects a config with
+/// `persist_path` set rather than silently starting from an empty store;
+/// use [`SoftwareEnclaveConfig::in_memory`] until it lands.
+#[derive(Clone, Debug, PartialEq, Eq, Zeroize)]
+pub struct SoftwareEnclaveConfig<A: AsRef<Path>> {
+    /// Path to the (future) encrypted-at-rest key store. If `None`, keys are memory-only
+    persist_path: Option<A>,
+}
+
+impl<A: AsRef<Path>> SoftwareEnclaveConfig<A> {
+    /// Create a memory-only software enclave configuration
+    pub fn in_memory() -> Self {
+        Self { persist_path: None }
+    }
+
+    /// Create a software enclave configuration that persists its (encrypted) store to `path`.
+    ///
+    /// Not yet implemented: `SoftwareEnclave::connect` currently returns a
+    /// `ConnectionFailure` for a config built this way.
+    pub fn persisted(path: A) -> Self {
+        Self {
+            persist_path: Some(path),
+        }
+    }
+}
+
+/// A fixed mapping from a key `label` to a slot on an ATECC508A/608A part.
+///
+/// Unlike the software enclave, slot contents and their key type are
+/// provisioned ahead of time and cannot be changed at runtime, so the
+/// `atecc` backend needs this table to know which physical slot — and which
+/// key type that slot was provisioned with — to use for a given label.
+#[derive(Clone, Debug, PartialEq, Eq, Zeroize)]
+pub struct AteccSlot {
+    /// Caller-facing label for the key, as used with `generate_key`/`public_key`/etc.
+    label: String,
+    /// The chip's fixed slot number (0-15) backing this label
+    slot: u8,
+    /// The key type this slot was provisioned with out-of-band. Not secret
+    /// material, so it's excluded from zeroization.
+    #[zeroize(skip)]
+    key_type: EnclaveKey,
+}
+
+impl AteccSlot {
+    /// Describe a slot on the part that backs `label`, already provisioned
+    /// out-of-band to hold a `key_type` key
+    pub fn new(label: impl Into<String>, slot: u8, key_type: EnclaveKey) -> Self {
+        Self {
+            label: label.into(),
+            slot,
+            key_type,
+        }
+    }
+}
+
+/// Configuration for an ATECC508A/608A secure element reached over I²C.
+#[derive(Clone, Debug, PartialEq, Eq, Zeroize)]
+pub struct AteccConfig<A: AsRef<Path>> {
+    /// Path to the I²C bus device, e.g. `/dev/i2c-1`
+    i2c_bus: A,
+    /// The 7-bit I²C address of the part, typically `0x60`
+    address: u8,
+    /// Static label-to-slot provisioning for this part
+    slots: Vec<AteccSlot>,
+}
+
+impl<A: AsRef<Path>> AteccConfig<A> {
+    /// Describe an ATECC part on `i2c_bus` at `address`, with `slots` already provisioned
+    pub fn new(i2c_bus: A, address: u8, slots: Vec<AteccSlot>) -> Self {
+        Self {
+            i2c_bus,
+            address,
+            slots,
+        }
+    }
+}
+
 #[cfg(any(target_os = "macos", target_os = "ios"))]
 pub mod macos;
+pub mod atecc;
+pub mod cmac;
+pub mod hpke;
+#[cfg(feature = "null-enclave")]
+pub mod null;
+pub mod software;
@@ -0,0 +1,200 @@
+/*
+ * Copyright 2020
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+//! Deterministic, non-secure test enclave for fuzzing and CI.
+//!
+//! `NullEnclave` implements the full [`EnclaveLike`] operation surface with
+//! fixed-seed stand-ins instead of real cryptography, so state machines and
+//! serialization paths that depend on an enclave can be exercised under
+//! `cargo fuzz` and unit tests without blocking on hardware or randomness.
+//! It still honors the `EnclaveError`/`EnclaveErrorKind` contracts (e.g.
+//! `ItemNotFound` for unknown handles) so error-handling code is covered
+//! too.
+//!
+//! Only ever compiled in behind the `null-enclave` feature — it must never
+//! be reachable from a production build.
+
+use super::{
+    EnclaveConfig, EnclaveErrorKind, EnclaveKey, EnclaveLike, EnclaveOps, EnclaveResult, KeyHandle,
+};
+use std::{collections::HashMap, path::Path, sync::Mutex};
+
+/// Tag prepended to "ciphertext" so `encrypt`/`decrypt` and `wrap_key`/
+/// `unwrap_key` round-trip without doing any real cryptography.
+const NULL_TAG: &[u8] = b"NULL-ENCLAVE-TAG";
+/// Fixed-seed 64-byte "signature" returned by `sign` for every message.
+const NULL_SIGNATURE: [u8; 64] = [0x42; 64];
+
+/// A deterministic, non-secure [`EnclaveLike`] implementation for fuzzing and CI.
+pub struct NullEnclave {
+    keys: Mutex<HashMap<String, EnclaveKey>>,
+}
+
+impl EnclaveLike for NullEnclave {
+    fn connect<A: AsRef<Path>, B: Into<String>>(config: EnclaveConfig<A, B>) -> EnclaveResult<Self> {
+        match config {
+            EnclaveConfig::NullEnclave => Ok(Self {
+                keys: Mutex::new(HashMap::new()),
+            }),
+            _ => Err(EnclaveErrorKind::ConnectionFailure {
+                msg: "NullEnclave::connect requires EnclaveConfig::NullEnclave".to_string(),
+            }
+            .into()),
+        }
+    }
+
+    fn close(self) {}
+}
+
+impl EnclaveOps for NullEnclave {
+    fn generate_key(&self, label: &str, key_type: EnclaveKey) -> EnclaveResult<KeyHandle> {
+        self.keys
+            .lock()
+            .expect("null enclave key store lock poisoned")
+            .insert(label.to_string(), key_type);
+        Ok(KeyHandle::new(label))
+    }
+
+    fn import_key(&self, label: &str, key_type: EnclaveKey, _material: &[u8]) -> EnclaveResult<KeyHandle> {
+        self.generate_key(label, key_type)
+    }
+
+    fn public_key(&self, handle: &KeyHandle) -> EnclaveResult<Vec<u8>> {
+        self.require(handle)?;
+        Ok(format!("null-public-key:{}", handle.id()).into_bytes())
+    }
+
+    fn sign(&self, handle: &KeyHandle, _data: &[u8]) -> EnclaveResult<Vec<u8>> {
+        self.require(handle)?;
+        Ok(NULL_SIGNATURE.to_vec())
+    }
+
+    fn verify(&self, handle: &KeyHandle, _data: &[u8], signature: &[u8]) -> EnclaveResult<bool> {
+        self.require(handle)?;
+        Ok(signature == NULL_SIGNATURE)
+    }
+
+    fn encrypt(&self, handle: &KeyHandle, plaintext: &[u8]) -> EnclaveResult<Vec<u8>> {
+        self.require(handle)?;
+        let mut out = NULL_TAG.to_vec();
+        out.extend_from_slice(plaintext);
+        Ok(out)
+    }
+
+    fn decrypt(&self, handle: &KeyHandle, ciphertext: &[u8]) -> EnclaveResult<Vec<u8>> {
+        self.require(handle)?;
+        ciphertext
+            .strip_prefix(NULL_TAG)
+            .map(|p| p.to_vec())
+            .ok_or_else(|| {
+                EnclaveErrorKind::GeneralError {
+                    msg: "null enclave ciphertext is missing its identity tag".to_string(),
+                }
+                .into()
+            })
+    }
+
+    fn wrap_key(&self, wrapping_key: &KeyHandle, target: &KeyHandle) -> EnclaveResult<Vec<u8>> {
+        self.require(wrapping_key)?;
+        self.require(target)?;
+        let mut out = NULL_TAG.to_vec();
+        out.extend_from_slice(target.id().as_bytes());
+        Ok(out)
+    }
+
+    fn unwrap_key(
+        &self,
+        wrapping_key: &KeyHandle,
+        label: &str,
+        key_type: EnclaveKey,
+        wrapped: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        self.require(wrapping_key)?;
+        wrapped.strip_prefix(NULL_TAG).ok_or_else(|| EnclaveErrorKind::GeneralError {
+            msg: "null enclave wrapped key is missing its identity tag".to_string(),
+        })?;
+        self.generate_key(label, key_type)
+    }
+
+    fn seal(
+        &self,
+        recipient_pub: &[u8],
+        _info: &[u8],
+        _aad: &[u8],
+        plaintext: &[u8],
+    ) -> EnclaveResult<(Vec<u8>, Vec<u8>)> {
+        let mut ciphertext = NULL_TAG.to_vec();
+        ciphertext.extend_from_slice(plaintext);
+        Ok((recipient_pub.to_vec(), ciphertext))
+    }
+
+    fn open(
+        &self,
+        handle: &KeyHandle,
+        _enc: &[u8],
+        _info: &[u8],
+        _aad: &[u8],
+        ciphertext: &[u8],
+    ) -> EnclaveResult<Vec<u8>> {
+        self.require(handle)?;
+        ciphertext
+            .strip_prefix(NULL_TAG)
+            .map(|p| p.to_vec())
+            .ok_or_else(|| {
+                EnclaveErrorKind::GeneralError {
+                    msg: "null enclave HPKE ciphertext is missing its identity tag".to_string(),
+                }
+                .into()
+            })
+    }
+
+    fn derive_key(
+        &self,
+        parent: &KeyHandle,
+        key_type: EnclaveKey,
+        context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        self.require(parent)?;
+        let label = format!("{}/derived/{}", parent.id(), context_info.len());
+        self.generate_key(&label, key_type)
+    }
+
+    fn derive_credential_key(
+        &self,
+        label: &str,
+        hmac_key: &KeyHandle,
+        _input: &[u8],
+        _context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        self.require(hmac_key)?;
+        self.generate_key(label, EnclaveKey::Ecdsa(super::EcCurves::Secp256r1, super::EcdsaAlgorithm::Sha256))
+    }
+}
+
+impl NullEnclave {
+    fn require(&self, handle: &KeyHandle) -> EnclaveResult<()> {
+        if self
+            .keys
+            .lock()
+            .expect("null enclave key store lock poisoned")
+            .contains_key(handle.id())
+        {
+            Ok(())
+        } else {
+            Err(EnclaveErrorKind::ItemNotFound.into())
+        }
+    }
+}
@@ -0,0 +1,755 @@
+/*
+ * Copyright 2020
+ *
+ * Licensed under the Apache License, Version 2.0 (the "License");
+ * you may not use this file except in compliance with the License.
+ * You may obtain a copy of the License at
+ *
+ *     http://www.apache.org/licenses/LICENSE-2.0
+ *
+ * Unless required by applicable law or agreed to in writing, software
+ * distributed under the License is distributed on an "AS IS" BASIS,
+ * WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+ * See the License for the specific language governing permissions and
+ * limitations under the License.
+ * -----------------------------------------------------------------------------
+ */
+//! Pure-software enclave backend built on RustCrypto primitives.
+//!
+//! This backend provides none of the tamper-resistance or key
+//! non-extractability guarantees of a hardware or OS-provided enclave, but
+//! implements the same [`EnclaveLike`] surface so that CI, tests, and
+//! platforms without an OS keyring or HSM can develop against the crate's
+//! public API. Keys are held in a `zeroize`-protected in-memory store;
+//! callers can later promote to a hardware-backed enclave by changing only
+//! their `EnclaveConfig`.
+//!
+//! `SoftwareEnclaveConfig::persisted` is not yet backed by an implementation:
+//! an encrypted-at-rest key store is on the roadmap, but `connect` rejects
+//! such a config today rather than silently starting from an empty store.
+
+use super::{
+    AesModes, AesSizes, EcCurves, EcdsaAlgorithm, EnclaveConfig, EnclaveError, EnclaveErrorKind,
+    EnclaveKey, EnclaveLike, EnclaveOps, EnclaveResult, HmacAlgorithm, KeyHandle, RsaMgf,
+    SoftwareEnclaveConfig, WrappingKey,
+};
+use aes_gcm::aead::{generic_array::GenericArray, Aead, NewAead};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use chacha20poly1305::XChaCha20Poly1305;
+use ed25519_dalek::Signer as _;
+use hmac::{Hmac, Mac, NewMac};
+use p256::ecdsa::signature::{Signer as _, Verifier as _};
+use rsa::{PaddingScheme, PublicKey as _};
+use sha2::{Sha256, Sha384, Sha512};
+use std::{collections::HashMap, path::Path, sync::Mutex};
+use zeroize::Zeroizing;
+
+/// Key material held by the software enclave, tagged by the `EnclaveKey`
+/// variant that produced it. Wrapped in `Zeroizing` where the inner bytes
+/// are raw secrets.
+enum StoredKey {
+    Ed25519(Box<ed25519_dalek::Keypair>),
+    EcdsaP256(Box<p256::ecdsa::SigningKey>),
+    EcdsaK256(Box<k256::ecdsa::SigningKey>),
+    RsaPss(Box<rsa::RsaPrivateKey>, RsaMgf),
+    RsaOaep(Box<rsa::RsaPrivateKey>, RsaMgf),
+    Hmac(Zeroizing<Vec<u8>>, HmacAlgorithm),
+    Cmac(Zeroizing<Vec<u8>>, AesSizes),
+    Wrap(Zeroizing<Vec<u8>>, WrappingKey),
+    X25519(Zeroizing<[u8; 32]>),
+}
+
+impl StoredKey {
+    fn key_kind(&self) -> &'static str {
+        match self {
+            StoredKey::Ed25519(_) => "Ed25519",
+            StoredKey::EcdsaP256(_) => "Ecdsa",
+            StoredKey::EcdsaK256(_) => "Ecdsa",
+            StoredKey::RsaPss(..) => "RsaPss",
+            StoredKey::RsaOaep(..) => "RsaOaep",
+            StoredKey::Hmac(..) => "Hmac",
+            StoredKey::Cmac(..) => "Cmac",
+            StoredKey::Wrap(..) => "WrapKey",
+            StoredKey::X25519(_) => "X25519",
+        }
+    }
+}
+
+/// A pure-software, RustCrypto-backed implementation of [`EnclaveLike`].
+pub struct SoftwareEnclave {
+    keys: Mutex<HashMap<String, StoredKey>>,
+}
+
+fn aes_key_len(size: AesSizes) -> usize {
+    match size {
+        AesSizes::Aes128 => 16,
+        AesSizes::Aes192 => 24,
+        AesSizes::Aes256 => 32,
+    }
+}
+
+fn unsupported(key_type: EnclaveKey) -> EnclaveError {
+    EnclaveErrorKind::GeneralError {
+        msg: format!(
+            "the software enclave does not support key type {:?}",
+            key_type
+        ),
+    }
+    .into()
+}
+
+fn item_not_found() -> EnclaveError {
+    EnclaveErrorKind::ItemNotFound.into()
+}
+
+/// Reject `WrapKey` variants the AEAD wrapper (`aead_encrypt_aad`/
+/// `aead_decrypt_aad`) can't actually carry out, so `generate_key`/
+/// `import_key` fail honestly up front instead of `wrap_key`/`unwrap_key`
+/// failing later with a confusing "not supported by the AEAD wrapper" error.
+fn requires_supported_wrap(wrapping: WrappingKey) -> EnclaveResult<()> {
+    match wrapping {
+        WrappingKey::Aes(AesSizes::Aes128, AesModes::Gcm)
+        | WrappingKey::Aes(AesSizes::Aes256, AesModes::Gcm)
+        | WrappingKey::XChachaPoly1305 => Ok(()),
+        other => Err(unsupported(EnclaveKey::WrapKey(other))),
+    }
+}
+
+impl EnclaveLike for SoftwareEnclave {
+    fn connect<A: AsRef<Path>, B: Into<String>>(
+        config: EnclaveConfig<A, B>,
+    ) -> EnclaveResult<Self> {
+        let persist_path = match config {
+            EnclaveConfig::SoftwareEnclave(SoftwareEnclaveConfig { persist_path }) => {
+                persist_path.map(|p| p.as_ref().to_path_buf())
+            }
+            _ => {
+                return Err(EnclaveErrorKind::ConnectionFailure {
+                    msg: "SoftwareEnclave::connect requires EnclaveConfig::SoftwareEnclave"
+                        .to_string(),
+                }
+                .into())
+            }
+        };
+
+        if let Some(path) = persist_path {
+            return Err(EnclaveErrorKind::ConnectionFailure {
+                msg: format!(
+                    "persistent software-enclave key stores are not yet implemented; \
+                     use SoftwareEnclaveConfig::in_memory() instead of persisting to {:?}",
+                    path
+                ),
+            }
+            .into());
+        }
+
+        Ok(Self {
+            keys: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn close(self) {
+        // Dropping `self` zeroizes every `StoredKey` via their `Zeroizing`
+        // and RustCrypto-internal `Drop` impls.
+    }
+}
+
+impl EnclaveOps for SoftwareEnclave {
+    fn generate_key(&self, label: &str, key_type: EnclaveKey) -> EnclaveResult<KeyHandle> {
+        let mut rng = rand::rngs::OsRng;
+        let stored = match key_type {
+            EnclaveKey::Ed25519 => {
+                StoredKey::Ed25519(Box::new(ed25519_dalek::Keypair::generate(&mut rng)))
+            }
+            EnclaveKey::Ecdsa(EcCurves::Secp256r1, _) => {
+                StoredKey::EcdsaP256(Box::new(p256::ecdsa::SigningKey::random(&mut rng)))
+            }
+            EnclaveKey::Ecdsa(EcCurves::Secp256k1, _) => {
+                StoredKey::EcdsaK256(Box::new(k256::ecdsa::SigningKey::random(&mut rng)))
+            }
+            EnclaveKey::RsaPss(mgf) => StoredKey::RsaPss(
+                Box::new(
+                    rsa::RsaPrivateKey::new(&mut rng, 2048)
+                        .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?,
+                ),
+                mgf,
+            ),
+            EnclaveKey::RsaOaep(mgf) => StoredKey::RsaOaep(
+                Box::new(
+                    rsa::RsaPrivateKey::new(&mut rng, 2048)
+                        .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?,
+                ),
+                mgf,
+            ),
+            EnclaveKey::Hmac(alg) => {
+                let mut key = vec![0u8; 32];
+                rand::RngCore::fill_bytes(&mut rng, &mut key);
+                StoredKey::Hmac(Zeroizing::new(key), alg)
+            }
+            EnclaveKey::X25519 => {
+                let sk = x25519_dalek::StaticSecret::new(&mut rng);
+                StoredKey::X25519(Zeroizing::new(sk.to_bytes()))
+            }
+            EnclaveKey::Cmac(size) => {
+                let mut key = vec![0u8; aes_key_len(size)];
+                rand::RngCore::fill_bytes(&mut rng, &mut key);
+                StoredKey::Cmac(Zeroizing::new(key), size)
+            }
+            EnclaveKey::WrapKey(wrapping) => {
+                requires_supported_wrap(wrapping)?;
+                let size = match wrapping {
+                    WrappingKey::Aes(AesSizes::Aes128, _) => 16,
+                    WrappingKey::Aes(AesSizes::Aes192, _) => 24,
+                    WrappingKey::Aes(AesSizes::Aes256, _) => 32,
+                    WrappingKey::XChachaPoly1305 => 32,
+                };
+                let mut key = vec![0u8; size];
+                rand::RngCore::fill_bytes(&mut rng, &mut key);
+                StoredKey::Wrap(Zeroizing::new(key), wrapping)
+            }
+            other => return Err(unsupported(other)),
+        };
+
+        self.keys
+            .lock()
+            .expect("software enclave key store lock poisoned")
+            .insert(label.to_string(), stored);
+        Ok(KeyHandle::new(label))
+    }
+
+    fn import_key(
+        &self,
+        label: &str,
+        key_type: EnclaveKey,
+        material: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        let stored = match key_type {
+            EnclaveKey::Ed25519 => {
+                let secret = ed25519_dalek::SecretKey::from_bytes(material)
+                    .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?;
+                let public = ed25519_dalek::PublicKey::from(&secret);
+                StoredKey::Ed25519(Box::new(ed25519_dalek::Keypair { secret, public }))
+            }
+            EnclaveKey::Ecdsa(EcCurves::Secp256r1, _) => {
+                let sk = p256::ecdsa::SigningKey::from_bytes(material)
+                    .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?;
+                StoredKey::EcdsaP256(Box::new(sk))
+            }
+            EnclaveKey::Ecdsa(EcCurves::Secp256k1, _) => {
+                let sk = k256::ecdsa::SigningKey::from_bytes(material)
+                    .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?;
+                StoredKey::EcdsaK256(Box::new(sk))
+            }
+            EnclaveKey::Hmac(alg) => StoredKey::Hmac(Zeroizing::new(material.to_vec()), alg),
+            EnclaveKey::WrapKey(wrapping) => {
+                requires_supported_wrap(wrapping)?;
+                StoredKey::Wrap(Zeroizing::new(material.to_vec()), wrapping)
+            }
+            EnclaveKey::X25519 => {
+                let bytes: [u8; 32] = material.try_into().map_err(|_| EnclaveErrorKind::GeneralError {
+                    msg: "X25519 key material must be 32 bytes".to_string(),
+                })?;
+                StoredKey::X25519(Zeroizing::new(bytes))
+            }
+            EnclaveKey::Cmac(size) => StoredKey::Cmac(Zeroizing::new(material.to_vec()), size),
+            other => return Err(unsupported(other)),
+        };
+
+        self.keys
+            .lock()
+            .expect("software enclave key store lock poisoned")
+            .insert(label.to_string(), stored);
+        Ok(KeyHandle::new(label))
+    }
+
+    fn public_key(&self, handle: &KeyHandle) -> EnclaveResult<Vec<u8>> {
+        let keys = self
+            .keys
+            .lock()
+            .expect("software enclave key store lock poisoned");
+        match keys.get(handle.id()).ok_or_else(item_not_found)? {
+            StoredKey::Ed25519(kp) => Ok(kp.public.as_bytes().to_vec()),
+            StoredKey::EcdsaP256(sk) => Ok(sk.verifying_key().to_encoded_point(false).as_bytes().to_vec()),
+            StoredKey::EcdsaK256(sk) => Ok(sk.verifying_key().to_encoded_point(false).as_bytes().to_vec()),
+            StoredKey::RsaPss(sk, _) | StoredKey::RsaOaep(sk, _) => {
+                Ok(rsa::RsaPublicKey::from(sk.as_ref()).to_pkcs1_der()
+                    .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?
+                    .as_der()
+                    .to_vec())
+            }
+            StoredKey::X25519(sk) => {
+                let sk = x25519_dalek::StaticSecret::from(**sk);
+                Ok(x25519_dalek::PublicKey::from(&sk).as_bytes().to_vec())
+            }
+            other => Err(EnclaveErrorKind::GeneralError {
+                msg: format!("{} keys have no public component", other.key_kind()),
+            }
+            .into()),
+        }
+    }
+
+    fn sign(&self, handle: &KeyHandle, data: &[u8]) -> EnclaveResult<Vec<u8>> {
+        let keys = self
+            .keys
+            .lock()
+            .expect("software enclave key store lock poisoned");
+        match keys.get(handle.id()).ok_or_else(item_not_found)? {
+            StoredKey::Ed25519(kp) => Ok(kp.sign(data).to_bytes().to_vec()),
+            StoredKey::EcdsaP256(sk) => {
+                let sig: p256::ecdsa::Signature = sk.sign(data);
+                Ok(sig.to_der().as_bytes().to_vec())
+            }
+            StoredKey::EcdsaK256(sk) => {
+                let sig: k256::ecdsa::Signature = sk.sign(data);
+                Ok(sig.to_der().as_bytes().to_vec())
+            }
+            StoredKey::RsaPss(sk, mgf) => sign_rsa_pss(sk, *mgf, data),
+            StoredKey::Hmac(key, alg) => Ok(hmac_tag(key, *alg, data)?),
+            StoredKey::Cmac(key, size) => Ok(super::cmac::mac(key, *size, data)?.to_vec()),
+            other => Err(EnclaveErrorKind::GeneralError {
+                msg: format!("{} keys cannot sign", other.key_kind()),
+            }
+            .into()),
+        }
+    }
+
+    fn verify(&self, handle: &KeyHandle, data: &[u8], signature: &[u8]) -> EnclaveResult<bool> {
+        let keys = self
+            .keys
+            .lock()
+            .expect("software enclave key store lock poisoned");
+        match keys.get(handle.id()).ok_or_else(item_not_found)? {
+            StoredKey::Ed25519(kp) => {
+                let sig = ed25519_dalek::Signature::from_bytes(signature)
+                    .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?;
+                Ok(kp.public.verify(data, &sig).is_ok())
+            }
+            StoredKey::EcdsaP256(sk) => {
+                let sig = p256::ecdsa::Signature::from_der(signature)
+                    .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?;
+                Ok(sk.verifying_key().verify(data, &sig).is_ok())
+            }
+            StoredKey::EcdsaK256(sk) => {
+                let sig = k256::ecdsa::Signature::from_der(signature)
+                    .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?;
+                Ok(sk.verifying_key().verify(data, &sig).is_ok())
+            }
+            StoredKey::Hmac(key, alg) => {
+                let expected = hmac_tag(key, *alg, data)?;
+                Ok(constant_time_eq(&expected, signature))
+            }
+            StoredKey::Cmac(key, size) => super::cmac::verify(key, *size, data, signature),
+            other => Err(EnclaveErrorKind::GeneralError {
+                msg: format!("{} keys cannot verify", other.key_kind()),
+            }
+            .into()),
+        }
+    }
+
+    fn encrypt(&self, handle: &KeyHandle, plaintext: &[u8]) -> EnclaveResult<Vec<u8>> {
+        let keys = self
+            .keys
+            .lock()
+            .expect("software enclave key store lock poisoned");
+        match keys.get(handle.id()).ok_or_else(item_not_found)? {
+            StoredKey::RsaOaep(sk, mgf) => {
+                let pk = rsa::RsaPublicKey::from(sk.as_ref());
+                let padding = oaep_padding(*mgf);
+                pk.encrypt(&mut rand::rngs::OsRng, padding, plaintext)
+                    .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() }.into())
+            }
+            other => Err(EnclaveErrorKind::GeneralError {
+                msg: format!("{} keys cannot encrypt", other.key_kind()),
+            }
+            .into()),
+        }
+    }
+
+    fn decrypt(&self, handle: &KeyHandle, ciphertext: &[u8]) -> EnclaveResult<Vec<u8>> {
+        let keys = self
+            .keys
+            .lock()
+            .expect("software enclave key store lock poisoned");
+        match keys.get(handle.id()).ok_or_else(item_not_found)? {
+            StoredKey::RsaOaep(sk, mgf) => {
+                let padding = oaep_padding(*mgf);
+                sk.decrypt(padding, ciphertext)
+                    .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() }.into())
+            }
+            other => Err(EnclaveErrorKind::GeneralError {
+                msg: format!("{} keys cannot decrypt", other.key_kind()),
+            }
+            .into()),
+        }
+    }
+
+    fn wrap_key(&self, wrapping_key: &KeyHandle, target: &KeyHandle) -> EnclaveResult<Vec<u8>> {
+        let keys = self
+            .keys
+            .lock()
+            .expect("software enclave key store lock poisoned");
+        let wrap_key = match keys
+            .get(wrapping_key.id())
+            .ok_or_else(item_not_found)?
+        {
+            StoredKey::Wrap(key, alg) => (key, *alg),
+            other => {
+                return Err(EnclaveErrorKind::GeneralError {
+                    msg: format!("{} keys cannot wrap", other.key_kind()),
+                }
+                .into())
+            }
+        };
+        let target_bytes = export_raw(
+            keys.get(target.id())
+                .ok_or_else(item_not_found)?,
+        )?;
+        aead_seal(wrap_key.0, wrap_key.1, &target_bytes)
+    }
+
+    fn unwrap_key(
+        &self,
+        wrapping_key: &KeyHandle,
+        label: &str,
+        key_type: EnclaveKey,
+        wrapped: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        let material = {
+            let keys = self
+                .keys
+                .lock()
+                .expect("software enclave key store lock poisoned");
+            let wrap_key = match keys
+                .get(wrapping_key.id())
+                .ok_or_else(item_not_found)?
+            {
+                StoredKey::Wrap(key, alg) => (key.clone(), *alg),
+                other => {
+                    return Err(EnclaveErrorKind::GeneralError {
+                        msg: format!("{} keys cannot unwrap", other.key_kind()),
+                    }
+                    .into())
+                }
+            };
+            aead_open(&wrap_key.0, wrap_key.1, wrapped)?
+        };
+        self.import_key(label, key_type, &material)
+    }
+
+    fn seal(
+        &self,
+        recipient_pub: &[u8],
+        info: &[u8],
+        aad: &[u8],
+        plaintext: &[u8],
+    ) -> EnclaveResult<(Vec<u8>, Vec<u8>)> {
+        super::hpke::seal(recipient_pub, info, aad, plaintext, WrappingKey::XChachaPoly1305)
+    }
+
+    fn open(
+        &self,
+        handle: &KeyHandle,
+        enc: &[u8],
+        info: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> EnclaveResult<Vec<u8>> {
+        let keys = self
+            .keys
+            .lock()
+            .expect("software enclave key store lock poisoned");
+        let sk = match keys.get(handle.id()).ok_or_else(item_not_found)? {
+            StoredKey::X25519(sk) => **sk,
+            other => {
+                return Err(EnclaveErrorKind::GeneralError {
+                    msg: format!("{} keys cannot be used for HPKE open", other.key_kind()),
+                }
+                .into())
+            }
+        };
+        drop(keys);
+        super::hpke::open(&sk, enc, info, aad, ciphertext, WrappingKey::XChachaPoly1305)
+    }
+
+    fn derive_key(
+        &self,
+        parent: &KeyHandle,
+        key_type: EnclaveKey,
+        context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        let parent_bytes = {
+            let keys = self
+                .keys
+                .lock()
+                .expect("software enclave key store lock poisoned");
+            export_raw(keys.get(parent.id()).ok_or_else(item_not_found)?)?
+        };
+        let material = hkdf_derive(&parent_bytes, context_info, key_material_len(key_type)?)?;
+        let label = format!("{}/derived/{}", parent.id(), hex_encode(context_info));
+        self.import_key(&label, key_type, &material)
+    }
+
+    fn derive_credential_key(
+        &self,
+        label: &str,
+        hmac_key: &KeyHandle,
+        input: &[u8],
+        context_info: &[u8],
+    ) -> EnclaveResult<KeyHandle> {
+        let tag = {
+            let keys = self.keys.lock().expect("software enclave key store lock poisoned");
+            match keys.get(hmac_key.id()).ok_or_else(item_not_found)? {
+                StoredKey::Hmac(key, HmacAlgorithm::Sha256) => hmac_tag(key, HmacAlgorithm::Sha256, input)?,
+                other => {
+                    return Err(EnclaveErrorKind::GeneralError {
+                        msg: format!("credential-bound derivation needs an Hmac(Sha256) key, got a {} key", other.key_kind()),
+                    }
+                    .into())
+                }
+            }
+        };
+        let sk = derive_p256_signing_key(&tag, context_info)?;
+        self.keys
+            .lock()
+            .expect("software enclave key store lock poisoned")
+            .insert(label.to_string(), StoredKey::EcdsaP256(Box::new(sk)));
+        Ok(KeyHandle::new(label))
+    }
+}
+
+/// Derive a P-256 signing-key scalar from `ikm`/`info` via HKDF, rejecting
+/// and re-deriving with an incrementing counter folded into `info` until the
+/// candidate lands in the valid range `[1, n)`.
+///
+/// `SigningKey::from_bytes` rejects HKDF output that is `>= n` or zero, so a
+/// single HKDF expand can't *guarantee* a valid, reproducible scalar for
+/// every input even though P-256's order is close enough to 2^256 that this
+/// loop will essentially always succeed on its first attempt.
+fn derive_p256_signing_key(ikm: &[u8], info: &[u8]) -> EnclaveResult<p256::ecdsa::SigningKey> {
+    for counter in 0u8..=255 {
+        let mut labeled_info = info.to_vec();
+        labeled_info.push(counter);
+        let candidate = hkdf_derive(ikm, &labeled_info, 32)?;
+        if let Ok(sk) = p256::ecdsa::SigningKey::from_bytes(&candidate) {
+            return Ok(sk);
+        }
+    }
+    Err(EnclaveErrorKind::GeneralError {
+        msg: "could not derive a valid P-256 scalar after 256 attempts".to_string(),
+    }
+    .into())
+}
+
+/// Number of raw bytes needed to construct a `key_type` key from derived
+/// HKDF output, for the key types `derive_key` can reasonably produce.
+fn key_material_len(key_type: EnclaveKey) -> EnclaveResult<usize> {
+    match key_type {
+        EnclaveKey::Hmac(_) => Ok(32),
+        EnclaveKey::X25519 => Ok(32),
+        EnclaveKey::WrapKey(WrappingKey::Aes(AesSizes::Aes128, _)) => Ok(16),
+        EnclaveKey::WrapKey(WrappingKey::Aes(AesSizes::Aes192, _)) => Ok(24),
+        EnclaveKey::WrapKey(WrappingKey::Aes(AesSizes::Aes256, _)) => Ok(32),
+        EnclaveKey::WrapKey(WrappingKey::XChachaPoly1305) => Ok(32),
+        EnclaveKey::Cmac(size) => Ok(aes_key_len(size)),
+        other => Err(unsupported(other)),
+    }
+}
+
+/// Hex-encode `bytes` so a derived key's context info can be folded into a
+/// stable, human-inspectable label.
+fn hex_encode(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// HKDF-SHA256 extract-then-expand: `prk = Extract(None, ikm)`, `okm = Expand(prk, info, len)`.
+fn hkdf_derive(ikm: &[u8], info: &[u8], len: usize) -> EnclaveResult<Vec<u8>> {
+    let hk = hkdf::Hkdf::<Sha256>::new(None, ikm);
+    let mut okm = vec![0u8; len];
+    hk.expand(info, &mut okm)
+        .map_err(|_| EnclaveErrorKind::GeneralError { msg: "HKDF expand output too long".to_string() })?;
+    Ok(okm)
+}
+
+/// Seal `plaintext` with a ready-derived AEAD key and nonce (no sequence
+/// counter applied, since HPKE single-shot only ever sends one message).
+/// Exposed so the `hpke` module can reuse this backend's AEAD wiring.
+pub(crate) fn seal_with_nonce(
+    key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+    aead: WrappingKey,
+) -> EnclaveResult<Vec<u8>> {
+    aead_encrypt_aad(key, aead, nonce, aad, plaintext)
+}
+
+/// Open a message produced by `seal_with_nonce`.
+pub(crate) fn open_with_nonce(
+    key: &[u8],
+    nonce: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+    aead: WrappingKey,
+) -> EnclaveResult<Vec<u8>> {
+    aead_decrypt_aad(key, aead, nonce, aad, ciphertext)
+}
+
+/// Raw key bytes extracted so they can be sealed under a `WrapKey`.
+/// Ed25519 keys export their raw 32-byte secret key; `Hmac` and `WrapKey`
+/// keys export their raw bytes. Every other key kind returns a
+/// `GeneralError`.
+fn export_raw(stored: &StoredKey) -> EnclaveResult<Zeroizing<Vec<u8>>> {
+    match stored {
+        StoredKey::Ed25519(kp) => Ok(Zeroizing::new(kp.secret.as_bytes().to_vec())),
+        StoredKey::Hmac(key, _) => Ok(Zeroizing::new(key.to_vec())),
+        StoredKey::Wrap(key, _) => Ok(Zeroizing::new(key.to_vec())),
+        other => Err(EnclaveErrorKind::GeneralError {
+            msg: format!("wrapping {} keys is not yet supported", other.key_kind()),
+        }
+        .into()),
+    }
+}
+
+fn oaep_padding(mgf: RsaMgf) -> PaddingScheme {
+    match mgf {
+        RsaMgf::Sha1 => PaddingScheme::new_oaep::<sha1::Sha1>(),
+        RsaMgf::Sha256 => PaddingScheme::new_oaep::<Sha256>(),
+        RsaMgf::Sha384 => PaddingScheme::new_oaep::<Sha384>(),
+        RsaMgf::Sha512 => PaddingScheme::new_oaep::<Sha512>(),
+    }
+}
+
+fn sign_rsa_pss(sk: &rsa::RsaPrivateKey, mgf: RsaMgf, data: &[u8]) -> EnclaveResult<Vec<u8>> {
+    let padding = match mgf {
+        RsaMgf::Sha1 => PaddingScheme::new_pss::<sha1::Sha1, _>(rand::rngs::OsRng),
+        RsaMgf::Sha256 => PaddingScheme::new_pss::<Sha256, _>(rand::rngs::OsRng),
+        RsaMgf::Sha384 => PaddingScheme::new_pss::<Sha384, _>(rand::rngs::OsRng),
+        RsaMgf::Sha512 => PaddingScheme::new_pss::<Sha512, _>(rand::rngs::OsRng),
+    };
+    sk.sign(padding, data)
+        .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() }.into())
+}
+
+fn hmac_tag(key: &[u8], alg: HmacAlgorithm, data: &[u8]) -> EnclaveResult<Vec<u8>> {
+    fn run<D: hmac::digest::Digest + hmac::digest::BlockInput + hmac::digest::FixedOutput + hmac::digest::Reset + Default + Clone>(
+        key: &[u8],
+        data: &[u8],
+    ) -> EnclaveResult<Vec<u8>> {
+        let mut mac = Hmac::<D>::new_from_slice(key)
+            .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() })?;
+        mac.update(data);
+        Ok(mac.finalize().into_bytes().to_vec())
+    }
+    match alg {
+        HmacAlgorithm::Sha1 => run::<sha1::Sha1>(key, data),
+        HmacAlgorithm::Sha256 => run::<Sha256>(key, data),
+        HmacAlgorithm::Sha384 => run::<Sha384>(key, data),
+        HmacAlgorithm::Sha512 => run::<Sha512>(key, data),
+    }
+}
+
+/// Constant-time byte comparison so MAC verification doesn't leak timing.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b.iter()).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn aead_seal(key: &[u8], alg: WrappingKey, plaintext: &[u8]) -> EnclaveResult<Vec<u8>> {
+    let mut nonce_bytes = vec![0u8; nonce_len(alg)];
+    rand::RngCore::fill_bytes(&mut rand::rngs::OsRng, &mut nonce_bytes);
+    let mut ciphertext = aead_encrypt(key, alg, &nonce_bytes, plaintext)?;
+    let mut out = nonce_bytes;
+    out.append(&mut ciphertext);
+    Ok(out)
+}
+
+fn aead_open(key: &[u8], alg: WrappingKey, wrapped: &[u8]) -> EnclaveResult<Zeroizing<Vec<u8>>> {
+    let n = nonce_len(alg);
+    if wrapped.len() < n {
+        return Err(EnclaveErrorKind::GeneralError {
+            msg: "wrapped key material is shorter than the AEAD nonce".to_string(),
+        }
+        .into());
+    }
+    let (nonce_bytes, ciphertext) = wrapped.split_at(n);
+    aead_decrypt(key, alg, nonce_bytes, ciphertext).map(Zeroizing::new)
+}
+
+fn nonce_len(alg: WrappingKey) -> usize {
+    match alg {
+        WrappingKey::Aes(_, _) => 12,
+        WrappingKey::XChachaPoly1305 => 24,
+    }
+}
+
+fn aead_encrypt(key: &[u8], alg: WrappingKey, nonce: &[u8], plaintext: &[u8]) -> EnclaveResult<Vec<u8>> {
+    aead_encrypt_aad(key, alg, nonce, b"", plaintext)
+}
+
+fn aead_decrypt(key: &[u8], alg: WrappingKey, nonce: &[u8], ciphertext: &[u8]) -> EnclaveResult<Vec<u8>> {
+    aead_decrypt_aad(key, alg, nonce, b"", ciphertext)
+}
+
+fn aead_encrypt_aad(
+    key: &[u8],
+    alg: WrappingKey,
+    nonce: &[u8],
+    aad: &[u8],
+    plaintext: &[u8],
+) -> EnclaveResult<Vec<u8>> {
+    let payload = aes_gcm::aead::Payload { msg: plaintext, aad };
+    match alg {
+        WrappingKey::Aes(AesSizes::Aes128, AesModes::Gcm) => {
+            Aes128Gcm::new(GenericArray::from_slice(key))
+                .encrypt(GenericArray::from_slice(nonce), payload)
+                .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() }.into())
+        }
+        WrappingKey::Aes(AesSizes::Aes256, AesModes::Gcm) => {
+            Aes256Gcm::new(GenericArray::from_slice(key))
+                .encrypt(GenericArray::from_slice(nonce), payload)
+                .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() }.into())
+        }
+        WrappingKey::XChachaPoly1305 => {
+            XChaCha20Poly1305::new(GenericArray::from_slice(key))
+                .encrypt(GenericArray::from_slice(nonce), payload)
+                .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() }.into())
+        }
+        other => Err(EnclaveErrorKind::GeneralError {
+            msg: format!("{:?} is not supported by the software enclave's AEAD wrapper", other),
+        }
+        .into()),
+    }
+}
+
+fn aead_decrypt_aad(
+    key: &[u8],
+    alg: WrappingKey,
+    nonce: &[u8],
+    aad: &[u8],
+    ciphertext: &[u8],
+) -> EnclaveResult<Vec<u8>> {
+    let payload = aes_gcm::aead::Payload { msg: ciphertext, aad };
+    match alg {
+        WrappingKey::Aes(AesSizes::Aes128, AesModes::Gcm) => {
+            Aes128Gcm::new(GenericArray::from_slice(key))
+                .decrypt(GenericArray::from_slice(nonce), payload)
+                .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() }.into())
+        }
+        WrappingKey::Aes(AesSizes::Aes256, AesModes::Gcm) => {
+            Aes256Gcm::new(GenericArray::from_slice(key))
+                .decrypt(GenericArray::from_slice(nonce), payload)
+                .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() }.into())
+        }
+        WrappingKey::XChachaPoly1305 => {
+            XChaCha20Poly1305::new(GenericArray::from_slice(key))
+                .decrypt(GenericArray::from_slice(nonce), payload)
+                .map_err(|e| EnclaveErrorKind::GeneralError { msg: e.to_string() }.into())
+        }
+        other => Err(EnclaveErrorKind::GeneralError {
+            msg: format!("{:?} is not supported by the software enclave's AEAD wrapper", other),
+        }
+        .into()),
+    }
+}